@@ -86,6 +86,51 @@ Blurs: [0.0, 0.0, 0.07, 0.12, 0.2, 0.12, 0.07, 0.0, 0.0]
 pub fn precomputed_lights() -> [f32; 9] {
     [4.0, 4.0, 4.0, 3.8, 5.0, 3.8, 4.0, 4.0, 4.0].map(|x| x / 4.0)
 }
+/// Builds the mirror-reflection permutation table used by `WALL_REFLECT`.
+///
+/// `refl[face][d]` gives the direction index that light travelling as global
+/// direction `d` is folded into after bouncing off a wall whose outward
+/// normal is `-rotate(face, IVec2::X)`. The outgoing vector for `d` has its
+/// component along the wall's normal negated (a mirror bounce off the wall)
+/// and is snapped to the closest direction that the discretization actually
+/// has.
+pub fn compute_reflection_table(angles: &[f32], directions: u32) -> Vec<Vec<u32>> {
+    let total_directions = directions * 4;
+
+    let dir_vector = |d: u32| -> (f32, f32) {
+        let wall_face = d / directions;
+        let i = d % directions;
+        let angle = angles[i as usize] + wall_face as f32 * PI / 2.0;
+        (angle.cos(), angle.sin())
+    };
+    let vectors: Vec<(f32, f32)> = (0..total_directions).map(dir_vector).collect();
+
+    let normals = [(-1.0, 0.0), (0.0, 1.0), (1.0, 0.0), (0.0, -1.0)];
+
+    let mut refl = vec![vec![0u32; total_directions as usize]; 4];
+    for (face, &(nx, ny)) in normals.iter().enumerate() {
+        for d in 0..total_directions {
+            let (cx, cy) = vectors[d as usize];
+            let dot = cx * nx + cy * ny;
+            let (rx, ry) = (cx - 2.0 * dot * nx, cy - 2.0 * dot * ny);
+
+            let closest = vectors
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    let da = (a.0 - rx).powi(2) + (a.1 - ry).powi(2);
+                    let db = (b.0 - rx).powi(2) + (b.1 - ry).powi(2);
+                    da.partial_cmp(&db).unwrap()
+                })
+                .unwrap()
+                .0;
+
+            refl[face][d as usize] = closest as u32;
+        }
+    }
+    refl
+}
+
 pub fn precomputed_slope_gathers(directions: u32) -> GatherData {
     assert!(directions == 9);
     let mut gathers = vec![];
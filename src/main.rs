@@ -1,33 +1,59 @@
 use std::collections::HashSet;
 use std::env::current_exe;
 use std::f32::consts::PI;
+use std::time::Instant;
 
 use glam::IVec2;
 use winit::dpi::PhysicalPosition;
-use winit::event::{ElementState, Event, KeyEvent, MouseButton, WindowEvent};
+use winit::event::{ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 
 use luisa::lang::types::vector::*;
 use luisa::prelude::*;
 use luisa_compute as luisa;
+use serde::{Deserialize, Serialize};
 use winit::keyboard::{KeyCode, PhysicalKey};
 
-use crate::light::{precomputed_lights, precomputed_slope_gathers};
+use crate::light::{compute_reflection_table, precomputed_lights, precomputed_slope_gathers};
 
 mod light;
 
 const GRID_SIZE: u32 = 64;
 const SCALING: u32 = 32;
-const SCALE_POWER: u32 = 5;
+
+// The on-screen region is decoupled from GRID_SIZE: GRID_SIZE may exceed it,
+// with the Camera choosing which slice of the grid is visible. WINDOW_SIZE is
+// a fixed viewport size and does not grow with GRID_SIZE.
+const WINDOW_SIZE: u32 = 1024;
 
 const DIRECTIONS: u32 = 9;
 const TOTAL_DIRECTIONS: u32 = DIRECTIONS * 4;
 
 const LIGHT_STEP: f32 = 0.1;
 const COLOR_STEP: f32 = 0.1;
+const HUE_STEP: f32 = PI / 12.0;
+
+const ZOOM_STEP: f32 = 0.1;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = (SCALING * 8) as f32;
+const PAN_STEP: f32 = 16.0;
+
+const TONE_MAP_NONE: u32 = 0;
+const TONE_MAP_REINHARD: u32 = 1;
+const TONE_MAP_ACES: u32 = 2;
+
+const WAVEFORM_SINE: u32 = 0;
+const WAVEFORM_SQUARE: u32 = 1;
+
+// Nominal duration of one update_kernel/emit_kernel iteration, used to
+// advance the tap-tempo clock. Ticking sim time per iteration (rather than
+// sampling a single wall-clock instant per frame) keeps the pulse envelope
+// in lockstep with the playback `speed` multiplier instead of freezing
+// within a fast-forwarded frame.
+const SIM_TICK_SECONDS: f32 = 1.0 / 60.0;
 
 const WALL_ABSORB: u32 = 0b01;
-// const WALL_REFLECT: u32 = 0b10;
+const WALL_REFLECT: u32 = 0b10;
 // TODO: Could actually use this for fog by allowing partial blurs.
 const WALL_BLUR: u32 = 0b100;
 const WALL_DIFFUSE: u32 = 0b10000;
@@ -42,6 +68,7 @@ enum Writer {
     Absorb,
     Blur,
     Diffuse,
+    Reflect,
 }
 impl Writer {
     fn wall(&self) -> u32 {
@@ -50,6 +77,7 @@ impl Writer {
             Self::Absorb => WALL_ABSORB,
             Self::Blur => WALL_BLUR,
             Self::Diffuse => WALL_DIFFUSE,
+            Self::Reflect => WALL_REFLECT,
         }
     }
 }
@@ -60,6 +88,142 @@ enum State {
     UpdatingLight,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Playback {
+    Running,
+    Paused,
+}
+
+// Envelope shape used to pulse emitters at the tapped tempo.
+#[derive(Debug, Copy, Clone)]
+enum Waveform {
+    Sine,
+    Square,
+}
+impl Waveform {
+    fn code(&self) -> u32 {
+        match self {
+            Self::Sine => WAVEFORM_SINE,
+            Self::Square => WAVEFORM_SQUARE,
+        }
+    }
+    fn toggle(&self) -> Self {
+        match self {
+            Self::Sine => Self::Square,
+            Self::Square => Self::Sine,
+        }
+    }
+}
+
+// Maps display pixels to world (grid) space: world = offset + display_pos / zoom.
+#[derive(Debug, Copy, Clone)]
+struct Camera {
+    offset: Vec2<f32>,
+    zoom: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum ToneMap {
+    None,
+    Reinhard,
+    Aces,
+}
+impl ToneMap {
+    fn code(&self) -> u32 {
+        match self {
+            Self::None => TONE_MAP_NONE,
+            Self::Reinhard => TONE_MAP_REINHARD,
+            Self::Aces => TONE_MAP_ACES,
+        }
+    }
+    fn next(&self) -> Self {
+        match self {
+            Self::None => Self::Reinhard,
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::None,
+        }
+    }
+}
+
+// A painted scene, flattened row-major for serialization. `emission` stores
+// TOTAL_DIRECTIONS entries per cell, each cell's entries contiguous and in
+// direction order (index = cell * TOTAL_DIRECTIONS + direction).
+#[derive(Debug, Serialize, Deserialize)]
+struct Scene {
+    walls: Vec<u32>,
+    colors: Vec<[f32; 4]>,
+    emission: Vec<[f32; 3]>,
+}
+
+// All of the editor's mutable input/UI state, threaded through
+// update_cursor/update_keyboard as a single `&mut` so new tools extend one
+// struct instead of growing another positional parameter list.
+#[derive(Debug, Copy, Clone)]
+struct EditorState {
+    light_color: Vec3<f32>,
+    emission: f32,
+    writer: Writer,
+    playback: Playback,
+    speed: u32,
+    step_once: bool,
+    camera: Camera,
+    tone_map: ToneMap,
+    save_requested: bool,
+    selected_channel: usize,
+    ctrl_held: bool,
+    scene_save_requested: bool,
+    scene_load_requested: bool,
+    last_tap: Option<Instant>,
+    pulse_period: f32,
+    waveform: Waveform,
+}
+impl Default for EditorState {
+    fn default() -> Self {
+        Self {
+            light_color: Vec3::new(1.0, 0.7, 0.2),
+            emission: 0.3,
+            writer: Writer::Absorb,
+            playback: Playback::Running,
+            speed: 1,
+            step_once: false,
+            camera: Camera {
+                offset: Vec2::new(0.0, 0.0),
+                zoom: SCALING as f32,
+            },
+            tone_map: ToneMap::None,
+            save_requested: false,
+            selected_channel: 0,
+            ctrl_held: false,
+            scene_save_requested: false,
+            scene_load_requested: false,
+            last_tap: None,
+            pulse_period: 0.0,
+            waveform: Waveform::Sine,
+        }
+    }
+}
+
+fn save_scene(
+    path: &str,
+    walls: &[u32],
+    colors: &[Vec4<f32>],
+    emission: &[Vec4<f32>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let scene = Scene {
+        walls: walls.to_vec(),
+        colors: colors.iter().map(|c| [c.x, c.y, c.z, c.w]).collect(),
+        emission: emission.iter().map(|c| [c.x, c.y, c.z]).collect(),
+    };
+    let ron = ron::ser::to_string_pretty(&scene, ron::ser::PrettyConfig::default())?;
+    std::fs::write(path, ron)?;
+    Ok(())
+}
+
+fn load_scene(path: &str) -> Result<Scene, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(ron::from_str(&text)?)
+}
+
 fn main() {
     luisa::init_logger();
     let ctx = Context::new(current_exe().unwrap());
@@ -67,10 +231,7 @@ fn main() {
 
     let event_loop = EventLoop::new().unwrap();
     let window = winit::window::WindowBuilder::new()
-        .with_inner_size(winit::dpi::PhysicalSize::new(
-            GRID_SIZE * SCALING,
-            GRID_SIZE * SCALING,
-        ))
+        .with_inner_size(winit::dpi::PhysicalSize::new(WINDOW_SIZE, WINDOW_SIZE))
         .with_resizable(false)
         .build(&event_loop)
         .unwrap();
@@ -78,18 +239,14 @@ fn main() {
     let swapchain = device.create_swapchain(
         &window,
         &device.default_stream(),
-        GRID_SIZE * SCALING,
-        GRID_SIZE * SCALING,
+        WINDOW_SIZE,
+        WINDOW_SIZE,
         false,
         false,
         3,
     );
-    let display = device.create_tex2d::<Vec4<f32>>(
-        swapchain.pixel_storage(),
-        GRID_SIZE * SCALING,
-        GRID_SIZE * SCALING,
-        1,
-    );
+    let display =
+        device.create_tex2d::<Vec4<f32>>(swapchain.pixel_storage(), WINDOW_SIZE, WINDOW_SIZE, 1);
 
     type Lights = Tex3d<Vec4<f32>>;
 
@@ -99,6 +256,8 @@ fn main() {
     let angles = gather_data.angles;
     assert!(angles.len() == DIRECTIONS as usize);
 
+    let refl = compute_reflection_table(&angles, DIRECTIONS);
+
     let lights_a = device.create_tex3d::<Vec4<f32>>(
         PixelStorage::Float4,
         GRID_SIZE,
@@ -125,48 +284,142 @@ fn main() {
     let walls = device.create_tex2d::<u32>(PixelStorage::Byte1, GRID_SIZE, GRID_SIZE, 1);
     let colors = device.create_tex2d::<Vec4<f32>>(PixelStorage::Float4, GRID_SIZE, GRID_SIZE, 1);
 
-    let draw_kernel = Kernel::<fn(Lights)>::new(
+    // Holds the raw, un-tonemapped HDR radiance so it can be read back and
+    // exported (see "save frame"), independent of whatever tone map the
+    // display is currently previewing.
+    let hdr_buffer = device.create_buffer::<Vec4<f32>>((WINDOW_SIZE * WINDOW_SIZE) as usize);
+
+    // Mirrors of walls/colors/emission for scene save (Ctrl+S): textures can't
+    // be read back to the host directly, so export_scene_kernel copies them
+    // here first.
+    let wall_buffer = device.create_buffer::<u32>((GRID_SIZE * GRID_SIZE) as usize);
+    let color_buffer = device.create_buffer::<Vec4<f32>>((GRID_SIZE * GRID_SIZE) as usize);
+    let scene_emission_buffer =
+        device.create_buffer::<Vec4<f32>>((GRID_SIZE * GRID_SIZE * TOTAL_DIRECTIONS) as usize);
+
+    let export_scene_kernel = Kernel::<fn()>::new(
+        &device,
+        &track!(|| {
+            let pos = dispatch_id().xy();
+            let index = pos.y * GRID_SIZE + pos.x;
+            wall_buffer.write(index, walls.read(pos));
+            color_buffer.write(index, colors.read(pos));
+            for d in 0..TOTAL_DIRECTIONS {
+                scene_emission_buffer
+                    .write(index * TOTAL_DIRECTIONS + d, emission.read(pos.extend(d)));
+            }
+        }),
+    );
+
+    // Inverse of export_scene_kernel: restores walls/colors/emission from the
+    // host-uploaded mirror buffers in a single bulk dispatch over the whole
+    // grid, rather than one kernel launch per cell.
+    let import_scene_kernel = Kernel::<fn()>::new(
+        &device,
+        &track!(|| {
+            let pos = dispatch_id().xy();
+            let index = pos.y * GRID_SIZE + pos.x;
+            walls.write(pos, wall_buffer.read(index));
+            colors.write(pos, color_buffer.read(index));
+            for d in 0..TOTAL_DIRECTIONS {
+                emission.write(pos.extend(d), scene_emission_buffer.read(index * TOTAL_DIRECTIONS + d));
+            }
+        }),
+    );
+
+    let draw_kernel = Kernel::<fn(Lights, Buffer<Vec4<f32>>, Vec2<f32>, f32, u32)>::new(
         &device,
-        &track!(|lights| {
+        &track!(|lights, hdr_buffer, offset, zoom, tone_map| {
             let display_pos = dispatch_id().xy();
-            let pos = display_pos >> SCALE_POWER;
+            let world = offset + display_pos.cast_f32() / zoom;
+            let cell = world.floor().cast_i32();
             let color = Vec3::<f32>::var_zeroed();
-            for i in 0..TOTAL_DIRECTIONS {
-                *color += lights.read(pos.extend(i)).xyz();
+
+            if cell.x >= 0
+                && cell.y >= 0
+                && cell.x < GRID_SIZE as i32
+                && cell.y < GRID_SIZE as i32
+            {
+                let pos = cell.cast_u32();
+                for i in 0..TOTAL_DIRECTIONS {
+                    *color += lights.read(pos.extend(i)).xyz();
+                }
+
+                // let wall_alpha = 0.0.var();
+                // let wall_color = Vec3::var_zeroed();
+                let w = walls.read(pos);
+                if w == WALL_ABSORB {
+                    *color = Vec3::new(0.2, 0.2, 0.2);
+                } else if w == WALL_DIFFUSE {
+                    *color = Vec3::splat(0.1) + color;
+                } else if w == WALL_REFLECT {
+                    *color = Vec3::new(0.05, 0.05, 0.15) + color;
+                }
             }
 
-            // let wall_alpha = 0.0.var();
-            // let wall_color = Vec3::var_zeroed();
-            let w = walls.read(pos);
-            if w == WALL_ABSORB {
-                *color = Vec3::new(0.2, 0.2, 0.2);
-            } else if w == WALL_DIFFUSE {
-                *color = Vec3::splat(0.1) + color;
+            let index = display_pos.y * WINDOW_SIZE + display_pos.x;
+            hdr_buffer.write(index, color.extend(1.0));
+
+            if tone_map == TONE_MAP_REINHARD {
+                *color = color / (Vec3::splat(1.0) + color);
+            } else if tone_map == TONE_MAP_ACES {
+                let a = 2.51;
+                let b = 0.03;
+                let c = 2.43;
+                let d = 0.59;
+                let e = 0.14;
+                *color =
+                    ((color * (color * a + b)) / (color * (color * c + d) + e)).clamp(
+                        Vec3::splat(0.0),
+                        Vec3::splat(1.0),
+                    );
             }
 
             display.write(display_pos, color.extend(1.0));
         }),
     );
 
-    let emit_kernel = Kernel::<fn(Lights)>::new(
+    // `time`/`period`/`waveform` pulse the *stored* emission at read time, so the
+    // base color painted by update_gradient_kernel never has to change: a
+    // period of 0 means "no tap-tempo yet", which is always a
+    // flat envelope of 1.0 (emitters behave as before tap-tempo existed).
+    let emit_kernel = Kernel::<fn(Lights, f32, f32, u32)>::new(
         &device,
-        &track!(|lights| {
+        &track!(|lights, time, period, waveform| {
             let pos = dispatch_id();
             let emission = emission.read(pos);
             if (emission != 0.0).any() {
-                lights.write(pos, emission);
+                let envelope = 1.0.var();
+                if period > 0.0 {
+                    let phase = time / period;
+                    let phase = phase - phase.floor();
+                    if waveform == WAVEFORM_SQUARE {
+                        if phase < 0.5 {
+                            *envelope = 1.0;
+                        } else {
+                            *envelope = 0.0;
+                        }
+                    } else {
+                        *envelope = 0.5 + 0.5 * (phase * 2.0 * PI).sin();
+                    }
+                }
+                lights.write(pos, emission * envelope);
             }
         }),
     );
 
-    let update_emission_kernel =
-        Kernel::<fn(Vec2<u32>, [Vec3<f32>; TOTAL_DIRECTIONS as usize])>::new(
+    // Paints a linear gradient of emission between two endpoints, used by the
+    // right-mouse drag tool (a single click is just a one-step gradient).
+    let update_gradient_kernel =
+        Kernel::<fn(Vec2<i32>, Vec2<i32>, u32, Vec3<f32>, Vec3<f32>)>::new(
             &device,
-            &track!(|pos, light| {
-                emission.write(
-                    pos.extend(dispatch_id().z),
-                    light[dispatch_id().z].extend(0.0),
-                );
+            &track!(|start, delta, count, color_a, color_b| {
+                let i = dispatch_id().x;
+                let steps = (count - 1).max(1);
+                let t = i.cast_f32() / steps.cast_f32();
+                let pos = (start + (delta.cast_f32() * t).cast_i32()).cast_u32();
+                let color = color_a * (1.0 - t) + color_b * t;
+                emission.write(pos.extend(dispatch_id().z), color.extend(0.0));
                 if dispatch_id().z == 0 {
                     walls.write(pos, 0);
                 }
@@ -185,6 +438,37 @@ fn main() {
         }),
     );
 
+    // Inverts the Camera transform to find the grid cell under a cursor
+    // position, or None if the cursor is outside the grid.
+    fn cursor_cell(cursor_pos: PhysicalPosition<f64>, camera: Camera) -> Option<Vec2<u32>> {
+        let world_x = camera.offset.x + cursor_pos.x as f32 / camera.zoom;
+        let world_y = camera.offset.y + cursor_pos.y as f32 / camera.zoom;
+        let (cell_x, cell_y) = (world_x.floor(), world_y.floor());
+        if cell_x < 0.0 || cell_y < 0.0 || cell_x >= GRID_SIZE as f32 || cell_y >= GRID_SIZE as f32
+        {
+            None
+        } else {
+            Some(Vec2::new(cell_x as u32, cell_y as u32))
+        }
+    }
+
+    // Rotates an RGB color around the (1,1,1) luma axis, per the standard
+    // hue-rotation matrix.
+    fn rotate_hue(c: Vec3<f32>, angle: f32) -> Vec3<f32> {
+        let cos_a = angle.cos();
+        let sin_a = angle.sin();
+        let sqrt3 = 3f32.sqrt();
+        let diag = cos_a + (1.0 - cos_a) / 3.0;
+        let off_plus = (1.0 - cos_a) / 3.0 + sin_a / sqrt3;
+        let off_minus = (1.0 - cos_a) / 3.0 - sin_a / sqrt3;
+
+        Vec3::new(
+            diag * c.x + off_minus * c.y + off_plus * c.z,
+            off_plus * c.x + diag * c.y + off_minus * c.z,
+            off_minus * c.x + off_plus * c.y + diag * c.z,
+        )
+    }
+
     fn rotate(face: u32, dir: IVec2) -> IVec2 {
         match face {
             // +x
@@ -223,7 +507,7 @@ fn main() {
                                 + Vec2::<i32>::from(rotate(face, gather.offset)))
                             .cast_u32();
                             let ow = walls.read(opos);
-                            if w == WALL_DIFFUSE || ow != WALL_DIFFUSE {
+                            if w == WALL_DIFFUSE || (ow != WALL_DIFFUSE && ow != WALL_REFLECT) {
                                 let transmission = transmissions[i] * lights.read(opos.extend(dir));
                                 *light[dir] += transmission;
                             }
@@ -239,10 +523,32 @@ fn main() {
 
             let delta_light = [Vec4::<f32>::splat(0.0); TOTAL_DIRECTIONS as usize].var();
 
+            let try_reflect = |face: u32| {
+                let offset = rotate(face, IVec2::X);
+                let wpos = (pos.cast_i32() + Vec2::<i32>::from(offset)).cast_u32();
+                if walls.read(wpos) == WALL_REFLECT {
+                    let wall_color = colors.read(wpos);
+                    let normal = (-offset.x as f32, -offset.y as f32);
+                    escape!({
+                        for d in 0..TOTAL_DIRECTIONS {
+                            let wall_face = d / DIRECTIONS;
+                            let i = d % DIRECTIONS;
+                            let angle = angles[i as usize] + wall_face as f32 * PI / 2.0;
+                            let dot = angle.cos() * normal.0 + angle.sin() * normal.1;
+                            if dot < 0.0 {
+                                track!({
+                                    *delta_light[refl[face as usize][d as usize] as usize] +=
+                                        light[d] * wall_color;
+                                });
+                            }
+                        }
+                    });
+                }
+            };
+
             let try_wall = |face: u32| {
                 let offset = rotate(face, IVec2::X);
                 let wpos = (pos.cast_i32() + Vec2::<i32>::from(offset)).cast_u32();
-                // TODO: Add reflect.
                 if walls.read(wpos) == WALL_DIFFUSE {
                     let wall_color = colors.read(wpos);
                     let gathered_light = Vec4::<f32>::var_zeroed();
@@ -293,6 +599,10 @@ fn main() {
                 try_wall(1);
                 try_wall(2);
                 try_wall(3);
+                try_reflect(0);
+                try_reflect(1);
+                try_reflect(2);
+                try_reflect(3);
             }
             if w == WALL_BLUR {
                 let total_light = Vec4::<f32>::var_zeroed();
@@ -315,94 +625,163 @@ fn main() {
 
     let mut parity = false;
 
+    // Tap-tempo clock, advanced by SIM_TICK_SECONDS per update/emit
+    // iteration rather than sampled from the wall clock (see RedrawRequested).
+    let mut sim_time: f32 = 0.0;
+
     let mut cursor_pos = PhysicalPosition::new(0.0, 0.0);
 
     let mut active_buttons = HashSet::new();
 
-    let mut light_color = Vec3::new(1.0, 0.7, 0.2);
-    let mut emission = 0.3;
+    // All editor/playback/camera/scene/tempo input state lives here so tools
+    // added on top don't grow update_cursor/update_keyboard's parameter list.
+    let mut editor = EditorState::default();
 
+    // The cell and light color captured when the right-mouse gradient drag began.
+    let mut gradient_start: Option<(Vec2<u32>, Vec3<f32>)> = None;
+
+    // Left-mouse wall painting is continuous while the button is held. Right-mouse
+    // emitter painting is handled separately as a press/drag/release gradient (see
+    // gradient_start below) so it can interpolate light_color across the drag.
     let mut update_cursor = |active_buttons: &HashSet<MouseButton>,
                              cursor_pos: PhysicalPosition<f64>,
-                             light_color: Vec3<f32>,
-                             emission: f32,
-                             writer: Writer| {
-        let pos = Vec2::new(
-            (cursor_pos.x as u32) >> SCALE_POWER,
-            (cursor_pos.y as u32) >> SCALE_POWER,
-        );
-        if active_buttons.contains(&MouseButton::Right) {
-            let total_light = [Vec3::new(
-                light_color.x * emission,
-                light_color.y * emission,
-                light_color.z * emission,
-            ); TOTAL_DIRECTIONS as usize];
-            update_emission_kernel.dispatch([1, 1, TOTAL_DIRECTIONS], &pos, &total_light);
-        } else if active_buttons.contains(&MouseButton::Left) {
-            let w = writer.wall();
-
-            update_wall_kernel.dispatch(
-                [1, 1, 1],
-                &pos,
-                &writer.wall(),
-                &if w == 0 {
-                    Vec3::splat(1.0)
-                } else {
-                    Vec3::splat(0.9)
-                },
-            );
+                             editor: &EditorState| {
+        if !active_buttons.contains(&MouseButton::Left) {
+            return;
         }
+        let Some(pos) = cursor_cell(cursor_pos, editor.camera) else {
+            return;
+        };
+        let w = editor.writer.wall();
+
+        update_wall_kernel.dispatch(
+            [1, 1, 1],
+            &pos,
+            &editor.writer.wall(),
+            &if w == 0 {
+                Vec3::splat(1.0)
+            } else {
+                Vec3::splat(0.9)
+            },
+        );
     };
     let update_cursor = &mut update_cursor;
 
     let mut state = State::Normal;
-    let mut writer = Writer::Absorb;
 
     /*
     E: Empty
     A: Absorb,
     B: Blur,
     D: Diffuse,
+    R: Reflect,
     */
 
-    let mut update_keyboard =
-        |ev: KeyEvent, light_color: &mut Vec3<f32>, emission: &mut f32, writer: &mut Writer| {
-            if ev.state != ElementState::Pressed {
-                return;
-            }
-            let PhysicalKey::Code(key) = ev.physical_key else {
-                panic!("Invalid")
-            };
+    let mut update_keyboard = |ev: KeyEvent, editor: &mut EditorState| {
+        let PhysicalKey::Code(key) = ev.physical_key else {
+            panic!("Invalid")
+        };
+
+        // Tracked across press/release (rather than only on press, like
+        // everything else below) so Ctrl+S/Ctrl+O still see it held.
+        if let KeyCode::ControlLeft | KeyCode::ControlRight = key {
+            editor.ctrl_held = ev.state == ElementState::Pressed;
+            return;
+        }
+
+        if ev.state != ElementState::Pressed {
+            return;
+        }
 
-            match state {
-                State::Normal => match key {
-                    KeyCode::KeyL => state = State::UpdatingLight,
-                    KeyCode::KeyE => *writer = Writer::Empty,
-                    KeyCode::KeyA => *writer = Writer::Absorb,
-                    KeyCode::KeyB => *writer = Writer::Blur,
-                    KeyCode::KeyD => *writer = Writer::Diffuse,
-                    _ => (),
-                },
-                State::UpdatingLight => match key {
-                    KeyCode::ArrowUp => {
-                        *emission += LIGHT_STEP;
+        match state {
+            State::Normal => match key {
+                KeyCode::KeyL => state = State::UpdatingLight,
+                KeyCode::KeyE => editor.writer = Writer::Empty,
+                KeyCode::KeyA => editor.writer = Writer::Absorb,
+                KeyCode::KeyB => editor.writer = Writer::Blur,
+                KeyCode::KeyD => editor.writer = Writer::Diffuse,
+                KeyCode::KeyR => editor.writer = Writer::Reflect,
+                KeyCode::Space => {
+                    editor.playback = match editor.playback {
+                        Playback::Running => Playback::Paused,
+                        Playback::Paused => Playback::Running,
+                    };
+                }
+                KeyCode::Period => {
+                    if editor.playback == Playback::Paused {
+                        editor.step_once = true;
                     }
-                    KeyCode::ArrowDown => {
-                        *emission -= LIGHT_STEP;
+                }
+                KeyCode::BracketRight => editor.speed += 1,
+                KeyCode::BracketLeft => editor.speed = (editor.speed - 1).max(1),
+                KeyCode::ArrowLeft => editor.camera.offset.x -= PAN_STEP / editor.camera.zoom,
+                KeyCode::ArrowRight => editor.camera.offset.x += PAN_STEP / editor.camera.zoom,
+                KeyCode::ArrowUp => editor.camera.offset.y -= PAN_STEP / editor.camera.zoom,
+                KeyCode::ArrowDown => editor.camera.offset.y += PAN_STEP / editor.camera.zoom,
+                KeyCode::KeyT => {
+                    editor.tone_map = editor.tone_map.next();
+                    println!("Tone map: {:?}", editor.tone_map);
+                }
+                KeyCode::F12 => editor.save_requested = true,
+                KeyCode::KeyS if editor.ctrl_held => editor.scene_save_requested = true,
+                KeyCode::KeyO if editor.ctrl_held => editor.scene_load_requested = true,
+                KeyCode::KeyP => {
+                    let now = Instant::now();
+                    if let Some(last) = editor.last_tap {
+                        editor.pulse_period = (now - last).as_secs_f32();
                     }
-                    KeyCode::Escape => state = State::Normal,
-                    _ => (),
-                },
-            }
-            match state {
-                State::Normal => {
-                    println!("{:?}", writer);
+                    editor.last_tap = Some(now);
+                }
+                _ => (),
+            },
+            State::UpdatingLight => match key {
+                KeyCode::ArrowUp => {
+                    editor.emission += LIGHT_STEP;
                 }
-                State::UpdatingLight => {
-                    println!("Emission: {:?}, Color: {:?}", *emission, *light_color);
+                KeyCode::ArrowDown => {
+                    editor.emission -= LIGHT_STEP;
                 }
+                KeyCode::ArrowLeft => editor.light_color = rotate_hue(editor.light_color, -HUE_STEP),
+                KeyCode::ArrowRight => editor.light_color = rotate_hue(editor.light_color, HUE_STEP),
+                KeyCode::Digit1 => editor.selected_channel = 0,
+                KeyCode::Digit2 => editor.selected_channel = 1,
+                KeyCode::Digit3 => editor.selected_channel = 2,
+                KeyCode::Equal => {
+                    let channel = match editor.selected_channel {
+                        0 => &mut editor.light_color.x,
+                        1 => &mut editor.light_color.y,
+                        _ => &mut editor.light_color.z,
+                    };
+                    *channel += COLOR_STEP;
+                }
+                KeyCode::Minus => {
+                    let channel = match editor.selected_channel {
+                        0 => &mut editor.light_color.x,
+                        1 => &mut editor.light_color.y,
+                        _ => &mut editor.light_color.z,
+                    };
+                    *channel -= COLOR_STEP;
+                }
+                KeyCode::KeyW => editor.waveform = editor.waveform.toggle(),
+                KeyCode::Escape => state = State::Normal,
+                _ => (),
+            },
+        }
+        match state {
+            State::Normal => {
+                println!(
+                    "{:?} | {:?} (speed {}) | pulse {:.2}s",
+                    editor.writer, editor.playback, editor.speed, editor.pulse_period
+                );
             }
-        };
+            State::UpdatingLight => {
+                println!(
+                    "Emission: {:?}, Color: {:?}, Channel: {}, Waveform: {:?}",
+                    editor.emission, editor.light_color, editor.selected_channel, editor.waveform
+                );
+            }
+        }
+    };
     let update_keyboard = &mut update_keyboard;
 
     update_wall_kernel.dispatch(
@@ -412,7 +791,10 @@ fn main() {
         &Vec3::splat(1.0),
     );
 
-    println!("{:?}", writer);
+    println!(
+        "{:?} | {:?} (speed {})",
+        editor.writer, editor.playback, editor.speed
+    );
 
     event_loop.set_control_flow(ControlFlow::Poll);
     event_loop
@@ -422,48 +804,175 @@ fn main() {
                     elwt.exit();
                 }
                 WindowEvent::RedrawRequested => {
-                    let lights = if parity { &lights_a } else { &lights_b };
-                    let next_lights = if parity { &lights_b } else { &lights_a };
-                    parity = !parity;
+                    let iterations = match editor.playback {
+                        Playback::Running => editor.speed,
+                        Playback::Paused => {
+                            if editor.step_once {
+                                editor.step_once = false;
+                                1
+                            } else {
+                                0
+                            }
+                        }
+                    };
                     {
                         let scope = device.default_stream().scope();
                         scope.present(&swapchain, &display);
-                        let commands = vec![
-                            update_kernel.dispatch_async(
+                        let mut commands = vec![];
+                        for _ in 0..iterations {
+                            sim_time += SIM_TICK_SECONDS;
+                            let lights = if parity { &lights_a } else { &lights_b };
+                            let next_lights = if parity { &lights_b } else { &lights_a };
+                            parity = !parity;
+                            commands.push(update_kernel.dispatch_async(
                                 [GRID_SIZE - 2, GRID_SIZE - 2, 1],
                                 lights,
                                 next_lights,
-                            ),
-                            emit_kernel.dispatch_async(
+                            ));
+                            commands.push(emit_kernel.dispatch_async(
                                 [GRID_SIZE, GRID_SIZE, TOTAL_DIRECTIONS],
                                 next_lights,
-                            ),
-                            draw_kernel.dispatch_async(
-                                [GRID_SIZE * SCALING, GRID_SIZE * SCALING, 1],
-                                next_lights,
-                            ),
-                        ];
+                                &sim_time,
+                                &editor.pulse_period,
+                                &editor.waveform.code(),
+                            ));
+                        }
+                        let current = if parity { &lights_a } else { &lights_b };
+                        commands.push(draw_kernel.dispatch_async(
+                            [WINDOW_SIZE, WINDOW_SIZE, 1],
+                            current,
+                            &hdr_buffer,
+                            &editor.camera.offset,
+                            &editor.camera.zoom,
+                            &editor.tone_map.code(),
+                        ));
                         scope.submit(commands);
                     }
+                    if editor.save_requested {
+                        editor.save_requested = false;
+                        device.default_stream().synchronize();
+                        let radiance = hdr_buffer.view(..).copy_to_vec();
+                        match export_frame(&radiance, WINDOW_SIZE, WINDOW_SIZE, editor.tone_map) {
+                            Ok(()) => println!("Saved capture.exr and capture.png"),
+                            Err(err) => eprintln!("Failed to export frame: {err}"),
+                        }
+                    }
                     window.request_redraw();
                 }
                 WindowEvent::CursorMoved { position, .. } => {
                     cursor_pos = position;
-                    update_cursor(&active_buttons, cursor_pos, light_color, emission, writer);
+                    update_cursor(&active_buttons, cursor_pos, &editor);
                 }
                 WindowEvent::MouseInput { button, state, .. } => {
                     match state {
                         ElementState::Pressed => {
                             active_buttons.insert(button);
+                            if button == MouseButton::Right {
+                                if let Some(pos) = cursor_cell(cursor_pos, editor.camera) {
+                                    gradient_start =
+                                        Some((pos, editor.light_color * editor.emission));
+                                }
+                            }
                         }
                         ElementState::Released => {
                             active_buttons.remove(&button);
+                            if button == MouseButton::Right {
+                                if let (Some((start, color_a)), Some(end)) =
+                                    (gradient_start, cursor_cell(cursor_pos, editor.camera))
+                                {
+                                    let color_b = editor.light_color * editor.emission;
+                                    let delta = Vec2::new(
+                                        end.x as i32 - start.x as i32,
+                                        end.y as i32 - start.y as i32,
+                                    );
+                                    let count =
+                                        delta.x.abs().max(delta.y.abs()) as u32 + 1;
+                                    update_gradient_kernel.dispatch(
+                                        [count, 1, TOTAL_DIRECTIONS],
+                                        &Vec2::new(start.x as i32, start.y as i32),
+                                        &delta,
+                                        &count,
+                                        &color_a,
+                                        &color_b,
+                                    );
+                                }
+                                gradient_start = None;
+                            }
                         }
                     }
-                    update_cursor(&active_buttons, cursor_pos, light_color, emission, writer);
+                    update_cursor(&active_buttons, cursor_pos, &editor);
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let scroll = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(p) => (p.y / 100.0) as f32,
+                    };
+                    let old_zoom = editor.camera.zoom;
+                    let new_zoom = (old_zoom * (1.0 + scroll * ZOOM_STEP)).clamp(MIN_ZOOM, MAX_ZOOM);
+                    let world = Vec2::new(
+                        editor.camera.offset.x + cursor_pos.x as f32 / old_zoom,
+                        editor.camera.offset.y + cursor_pos.y as f32 / old_zoom,
+                    );
+                    editor.camera.offset = Vec2::new(
+                        world.x - cursor_pos.x as f32 / new_zoom,
+                        world.y - cursor_pos.y as f32 / new_zoom,
+                    );
+                    editor.camera.zoom = new_zoom;
                 }
                 WindowEvent::KeyboardInput { event, .. } => {
-                    update_keyboard(event, &mut light_color, &mut emission, &mut writer);
+                    update_keyboard(event, &mut editor);
+
+                    if editor.scene_save_requested {
+                        editor.scene_save_requested = false;
+                        export_scene_kernel.dispatch([GRID_SIZE, GRID_SIZE, 1]);
+                        device.default_stream().synchronize();
+                        let walls_host = wall_buffer.view(..).copy_to_vec();
+                        let colors_host = color_buffer.view(..).copy_to_vec();
+                        let emission_host = scene_emission_buffer.view(..).copy_to_vec();
+                        match save_scene("scene.ron", &walls_host, &colors_host, &emission_host) {
+                            Ok(()) => println!("Saved scene to scene.ron"),
+                            Err(e) => println!("Failed to save scene: {:?}", e),
+                        }
+                    }
+
+                    if editor.scene_load_requested {
+                        editor.scene_load_requested = false;
+                        match load_scene("scene.ron") {
+                            Ok(scene) => {
+                                let cells = (GRID_SIZE * GRID_SIZE) as usize;
+                                let emission_len = cells * TOTAL_DIRECTIONS as usize;
+                                if scene.walls.len() != cells
+                                    || scene.colors.len() != cells
+                                    || scene.emission.len() != emission_len
+                                {
+                                    println!(
+                                        "Failed to load scene: scene.ron doesn't match this grid size (expected {} cells / {} emission entries, got {} / {})",
+                                        cells,
+                                        emission_len,
+                                        scene.walls.len(),
+                                        scene.emission.len()
+                                    );
+                                } else {
+                                    let colors_host: Vec<Vec4<f32>> = scene
+                                        .colors
+                                        .iter()
+                                        .map(|c| Vec4::new(c[0], c[1], c[2], c[3]))
+                                        .collect();
+                                    let emission_host: Vec<Vec4<f32>> = scene
+                                        .emission
+                                        .iter()
+                                        .map(|c| Vec4::new(c[0], c[1], c[2], 0.0))
+                                        .collect();
+                                    wall_buffer.view(..).copy_from(&scene.walls);
+                                    color_buffer.view(..).copy_from(&colors_host);
+                                    scene_emission_buffer.view(..).copy_from(&emission_host);
+                                    import_scene_kernel.dispatch([GRID_SIZE, GRID_SIZE, 1]);
+                                    println!("Loaded scene from scene.ron");
+                                }
+                            }
+                            Err(e) => println!("Failed to load scene: {:?}", e),
+                        }
+                    }
                 }
                 _ => (),
             },
@@ -474,3 +983,48 @@ fn main() {
         })
         .unwrap();
 }
+
+fn tonemap_reinhard(c: [f32; 3]) -> [f32; 3] {
+    c.map(|x| x / (1.0 + x))
+}
+
+fn tonemap_aces(c: [f32; 3]) -> [f32; 3] {
+    const A: f32 = 2.51;
+    const B: f32 = 0.03;
+    const C: f32 = 2.43;
+    const D: f32 = 0.59;
+    const E: f32 = 0.14;
+    c.map(|x| (x * (A * x + B) / (x * (C * x + D) + E)).clamp(0.0, 1.0))
+}
+
+// Writes the linear HDR radiance to a full-precision `.exr`, and the same
+// frame tone-mapped to an 8-bit `.png`, from a single host-side readback of
+// `hdr_buffer`.
+fn export_frame(
+    radiance: &[Vec4<f32>],
+    width: u32,
+    height: u32,
+    tone_map: ToneMap,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let width = width as usize;
+    let height = height as usize;
+
+    exr::prelude::write_rgb_file("capture.exr", width, height, |x, y| {
+        let c = radiance[y * width + x];
+        (c.x, c.y, c.z)
+    })?;
+
+    let mut img = image::RgbImage::new(width as u32, height as u32);
+    for (i, c) in radiance.iter().enumerate() {
+        let mapped = match tone_map {
+            ToneMap::None => [c.x, c.y, c.z],
+            ToneMap::Reinhard => tonemap_reinhard([c.x, c.y, c.z]),
+            ToneMap::Aces => tonemap_aces([c.x, c.y, c.z]),
+        };
+        let pixel = mapped.map(|v| (v.clamp(0.0, 1.0) * 255.0) as u8);
+        img.put_pixel((i % width) as u32, (i / width) as u32, image::Rgb(pixel));
+    }
+    img.save("capture.png")?;
+
+    Ok(())
+}